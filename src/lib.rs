@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env::var as env_var;
 use std::fs;
 use std::fs::File;
@@ -7,25 +7,61 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use chrono::{DateTime, Duration, Utc};
 use dirs::home_dir;
 use regex::Regex;
 use rusoto_core::{HttpClient, Region};
 use rusoto_credential::{
-    AutoRefreshingProvider, AwsCredentials, CredentialsError, ProfileProvider,
-    ProvideAwsCredentials,
+    AutoRefreshingProvider, AwsCredentials, ContainerProvider, CredentialsError,
+    EnvironmentProvider, InstanceMetadataProvider, ProvideAwsCredentials, StaticProvider,
 };
-use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
+use rusoto_sts::{
+    AssumeRoleRequest, GetCallerIdentityRequest, Sts, StsAssumeRoleSessionCredentialsProvider,
+    StsClient,
+};
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 const SOURCE_PROFILE: &str = "source_profile";
 const ROLE_ARN: &str = "role_arn";
+const MFA_SERIAL: &str = "mfa_serial";
+const DURATION_SECONDS: &str = "duration_seconds";
+const EXTERNAL_ID: &str = "external_id";
+const ROLE_SESSION_NAME: &str = "role_session_name";
+const POLICY: &str = "policy";
+const CREDENTIAL_SOURCE: &str = "credential_source";
+
+const CREDENTIAL_SOURCE_EC2_INSTANCE_METADATA: &str = "Ec2InstanceMetadata";
+const CREDENTIAL_SOURCE_ECS_CONTAINER: &str = "EcsContainer";
+const CREDENTIAL_SOURCE_ENVIRONMENT: &str = "Environment";
+
+const AWS_ACCESS_KEY_ID: &str = "aws_access_key_id";
+const AWS_SECRET_ACCESS_KEY: &str = "aws_secret_access_key";
+const AWS_SESSION_TOKEN: &str = "aws_session_token";
+
+/// Hard cap on `source_profile` chain length, so a malformed config with a
+/// very long (but non-cyclic) chain can't recurse forever.
+const MAX_SOURCE_PROFILE_DEPTH: usize = 10;
+
+/// A cache entry is treated as expired this many seconds before its actual
+/// expiry, to leave headroom for the request that will use the credentials.
+const CREDENTIAL_CACHE_EXPIRY_SKEW_SECONDS: i64 = 60;
+
+/// A closure invoked to obtain the one-time MFA token code when the selected
+/// profile declares an `mfa_serial`.
+pub type MfaTokenProvider = Arc<dyn Fn() -> Result<String, CredentialsError> + Send + Sync>;
 
 #[derive(Clone)]
 pub struct ConfigAssumeRoleProvider {
     default_region: Region,
     session_name: String,
-    sts_assume_role_provider:
-        Arc<RwLock<Option<AutoRefreshingProvider<StsAssumeRoleSessionCredentialsProvider>>>>,
+    profile_name: Option<String>,
+    mfa_token_provider: Option<MfaTokenProvider>,
+    cache_dir: Option<PathBuf>,
+    // Caches both the resolved provider and the region its AssumeRole call
+    // hit, so `verify()` can query the same STS endpoint instead of assuming
+    // `default_region`.
+    assumed_role_state: Arc<RwLock<Option<(Box<dyn ProvideAwsCredentials + Send + Sync>, Region)>>>,
 }
 
 impl ConfigAssumeRoleProvider {
@@ -33,9 +69,84 @@ impl ConfigAssumeRoleProvider {
         Self {
             default_region,
             session_name,
-            sts_assume_role_provider: Arc::new(RwLock::new(None)),
+            profile_name: None,
+            mfa_token_provider: None,
+            cache_dir: None,
+            assumed_role_state: Arc::new(RwLock::new(None)),
         }
     }
+
+    /// Supply a closure used to obtain the six-digit MFA code when the
+    /// resolved profile has an `mfa_serial` entry.
+    pub fn with_mfa_token_provider(mut self, mfa_token_provider: MfaTokenProvider) -> Self {
+        self.mfa_token_provider = Some(mfa_token_provider);
+        self
+    }
+
+    /// Target a specific profile instead of `default_profile_name()`
+    /// (`$AWS_PROFILE`, or `"default"`), so a single process can construct
+    /// providers for several profiles.
+    pub fn with_profile_name(mut self, profile_name: String) -> Self {
+        self.profile_name = Some(profile_name);
+        self
+    }
+
+    /// Opt into caching assumed-role credentials on disk under
+    /// `~/.aws/cli/cache`, like the AWS CLI does, so a new process can reuse
+    /// them instead of re-running AssumeRole (and re-prompting for MFA).
+    pub fn with_credential_cache(mut self) -> Self {
+        self.cache_dir = default_credential_cache_dir().ok();
+        self
+    }
+
+    /// Same as [`Self::with_credential_cache`], but caching to a
+    /// caller-chosen directory instead of the default `~/.aws/cli/cache`.
+    pub fn with_credential_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    /// Confirms the resolved credentials actually work by issuing a
+    /// `GetCallerIdentity` request, so wiring mistakes in `role_arn` /
+    /// `source_profile` are caught at startup rather than on the first real
+    /// API call.
+    pub async fn verify(&self) -> Result<CallerIdentity, CredentialsError> {
+        let credentials = self.credentials().await?;
+        // `credentials()` above has populated `assumed_role_state`, so this
+        // is the region the AssumeRole call that produced `credentials`
+        // actually hit, not necessarily `self.default_region`.
+        let region = {
+            let reader_lock = self.assumed_role_state.read().await;
+            reader_lock
+                .as_ref()
+                .map(|(_, region)| region.clone())
+                .unwrap_or_else(|| self.default_region.clone())
+        };
+        let sts = StsClient::new_with(
+            HttpClient::new().unwrap(),
+            StaticProvider::from(credentials),
+            region,
+        );
+        let response = sts
+            .get_caller_identity(GetCallerIdentityRequest {})
+            .await
+            .map_err(|err| {
+                CredentialsError::new(format!("Failed to call GetCallerIdentity: {}", err))
+            })?;
+        Ok(CallerIdentity {
+            account: response.account,
+            arn: response.arn,
+            user_id: response.user_id,
+        })
+    }
+}
+
+/// The identity returned by a successful [`ConfigAssumeRoleProvider::verify`] call.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallerIdentity {
+    pub account: Option<String>,
+    pub arn: Option<String>,
+    pub user_id: Option<String>,
 }
 
 impl Default for ConfigAssumeRoleProvider {
@@ -44,7 +155,10 @@ impl Default for ConfigAssumeRoleProvider {
             default_region: Region::default(),
             session_name: concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"))
                 .to_string(),
-            sts_assume_role_provider: Arc::new(RwLock::new(None)),
+            profile_name: None,
+            mfa_token_provider: None,
+            cache_dir: None,
+            assumed_role_state: Arc::new(RwLock::new(None)),
         }
     }
 }
@@ -53,17 +167,22 @@ impl Default for ConfigAssumeRoleProvider {
 impl ProvideAwsCredentials for ConfigAssumeRoleProvider {
     async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
         {
-            let reader_lock = self.sts_assume_role_provider.read().await;
-            if let Some(provider) = &*reader_lock {
+            let reader_lock = self.assumed_role_state.read().await;
+            if let Some((provider, _)) = &*reader_lock {
                 return provider.credentials().await;
             }
         }
-        let provider =
-            create_assume_role_profile(self.default_region.clone(), self.session_name.clone())?;
-        let mut writer_lock = self.sts_assume_role_provider.write().await;
-        *writer_lock = Some(provider);
+        let state = create_assume_role_profile(
+            self.default_region.clone(),
+            self.session_name.clone(),
+            self.mfa_token_provider.clone(),
+            self.profile_name.clone(),
+            self.cache_dir.clone(),
+        )?;
+        let mut writer_lock = self.assumed_role_state.write().await;
+        *writer_lock = Some(state);
         let reader_lock = writer_lock.downgrade();
-        if let Some(provider) = &*reader_lock {
+        if let Some((provider, _)) = &*reader_lock {
             return provider.credentials().await;
         } else {
             unreachable!()
@@ -100,41 +219,453 @@ impl ProvideAwsCredentials for ConfigAssumeRoleProvider {
 fn create_assume_role_profile(
     default_region: Region,
     session_name: String,
-) -> Result<AutoRefreshingProvider<StsAssumeRoleSessionCredentialsProvider>, CredentialsError> {
-    let config = parse_config_file(default_profile_location()?.as_path())
-        .ok_or_else(|| CredentialsError::new("Failed to parse config file"))?;
-    let source_profile_name = config
-        .get(&default_profile_name())
-        .and_then(|props| props.get(SOURCE_PROFILE))
-        .map(std::borrow::ToOwned::to_owned)
-        .ok_or_else(|| CredentialsError::new("Failed to find source_profile in config file"))?;
-    let role_arn = config
-        .get(&default_profile_name())
-        .and_then(|props| props.get(ROLE_ARN))
+    mfa_token_provider: Option<MfaTokenProvider>,
+    profile_name: Option<String>,
+    cache_dir: Option<PathBuf>,
+) -> Result<(Box<dyn ProvideAwsCredentials + Send + Sync>, Region), CredentialsError> {
+    let config = parse_config_file(config_file_location()?.as_path()).unwrap_or_default();
+    let credentials =
+        parse_config_file(credentials_file_location()?.as_path()).unwrap_or_default();
+    let merged = merge_config_maps(config, credentials);
+    let resolved_profile_name = profile_name.unwrap_or_else(default_profile_name);
+
+    // Only profiles that actually assume a role benefit from caching; a leaf
+    // profile's static credentials are already "cached" in the config files.
+    // Key on the session params this profile actually resolves to (not just
+    // the provider-wide default `session_name`), so two profiles assuming
+    // the same `role_arn` under different scope-down policies/external ids
+    // don't collide on the same cache entry.
+    let cache_key = merged
+        .get(&resolved_profile_name)
+        .and_then(|profile| profile.get(ROLE_ARN).map(|role_arn| (role_arn, profile)))
+        .map(|(role_arn, profile)| -> Result<String, CredentialsError> {
+            let params = parse_session_params(profile, &session_name)?;
+            Ok(credential_cache_key(role_arn, &params))
+        })
+        .transpose()?;
+
+    let mut visited = HashSet::new();
+    let (provider, region) = resolve_credentials_provider(
+        &resolved_profile_name,
+        &merged,
+        &default_region,
+        &session_name,
+        mfa_token_provider.as_ref(),
+        &mut visited,
+        0,
+    )?;
+    // A leaf profile never assumes a role, so there's no per-hop region to
+    // report; fall back to the caller's default.
+    let region = region.unwrap_or(default_region);
+
+    let provider: Box<dyn ProvideAwsCredentials + Send + Sync> = match (cache_dir, cache_key) {
+        (Some(cache_dir), Some(cache_key)) => Box::new(CachingCredentialsProvider {
+            inner: provider,
+            cache_dir,
+            cache_key,
+        }),
+        _ => provider,
+    };
+    Ok((provider, region))
+}
+
+/// The AWS CLI's own default cache directory (`~/.aws/cli/cache`).
+fn default_credential_cache_dir() -> Result<PathBuf, CredentialsError> {
+    let mut dir = home_dir()
+        .ok_or_else(|| CredentialsError::new("Failed to determine home directory."))?;
+    dir.push(".aws");
+    dir.push("cli");
+    dir.push("cache");
+    Ok(dir)
+}
+
+// The resolved per-hop values that actually scope an AssumeRole call, shared
+// between cache-key derivation (here) and the real call (`resolve_credentials_provider`)
+// so the two can never drift apart.
+#[derive(Clone)]
+struct SessionParams {
+    role_session_name: String,
+    external_id: Option<String>,
+    policy: Option<String>,
+    duration_seconds: Option<i64>,
+}
+
+fn parse_session_params(
+    profile: &HashMap<String, String>,
+    default_session_name: &str,
+) -> Result<SessionParams, CredentialsError> {
+    let role_session_name = profile
+        .get(ROLE_SESSION_NAME)
         .map(std::borrow::ToOwned::to_owned)
-        .ok_or_else(|| CredentialsError::new("Failed to find role_arn in config file"))?;
-    let source_profile = ProfileProvider::with_default_credentials(source_profile_name)?;
-    let source_profile_region_string = source_profile.region_from_profile().unwrap_or(None);
-    let source_profile_region = if let Some(s) = source_profile_region_string {
-        Region::from_str(&s).unwrap_or(default_region)
-    } else {
-        default_region
+        .unwrap_or_else(|| default_session_name.to_owned());
+    let external_id = profile.get(EXTERNAL_ID).map(std::borrow::ToOwned::to_owned);
+    let policy = profile.get(POLICY).map(std::borrow::ToOwned::to_owned);
+    let duration_seconds = profile
+        .get(DURATION_SECONDS)
+        .map(|value| {
+            value.parse::<i64>().map_err(|_| {
+                CredentialsError::new(format!(
+                    "Failed to parse duration_seconds \"{}\" as an integer",
+                    value
+                ))
+            })
+        })
+        .transpose()?;
+    Ok(SessionParams {
+        role_session_name,
+        external_id,
+        policy,
+        duration_seconds,
+    })
+}
+
+// Keyed on every param that actually scopes the AssumeRole call, not just
+// `role_arn`: two profiles assuming the same role under different
+// `external_id`/`policy`/`duration_seconds` (or a different
+// `role_session_name`) must not collide on the same cache file, and a
+// profile whose `policy` changes must not keep serving creds scoped to the
+// old policy.
+fn credential_cache_key(role_arn: &str, params: &SessionParams) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    params.role_session_name.hash(&mut hasher);
+    params.external_id.hash(&mut hasher);
+    params.policy.hash(&mut hasher);
+    params.duration_seconds.hash(&mut hasher);
+    let params_hash = hasher.finish();
+
+    let sanitized_role_arn: String = role_arn
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}_{:016x}", sanitized_role_arn, params_hash)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<DateTime<Utc>>,
+}
+
+fn credential_cache_file_path(cache_dir: &Path, cache_key: &str) -> PathBuf {
+    cache_dir.join(format!("{}.json", cache_key))
+}
+
+// Reads a cached credentials entry, treating it as absent if the file is
+// missing, unreadable, corrupt, or expired (within the skew margin of its
+// actual expiry) rather than failing the caller. Runs on `tokio::fs` since
+// `CachingCredentialsProvider::credentials` calls this on every outgoing
+// request, not just on a genuine role refresh, and a blocking `std::fs` read
+// there would stall the executor on every call.
+async fn read_credentials_cache(cache_dir: &Path, cache_key: &str) -> Option<AwsCredentials> {
+    let contents = tokio::fs::read_to_string(credential_cache_file_path(cache_dir, cache_key))
+        .await
+        .ok()?;
+    let cached: CachedCredentials = serde_json::from_str(&contents).ok()?;
+    let expiration = cached.expiration?;
+    if expiration <= Utc::now() + Duration::seconds(CREDENTIAL_CACHE_EXPIRY_SKEW_SECONDS) {
+        return None;
+    }
+    Some(AwsCredentials::new(
+        cached.access_key_id,
+        cached.secret_access_key,
+        cached.session_token,
+        Some(expiration),
+    ))
+}
+
+// Best-effort write: a cache directory we can't create or a write failure
+// just means the next process will assume the role again. Runs on
+// `tokio::fs` for the same reason as `read_credentials_cache`.
+async fn write_credentials_cache(cache_dir: &Path, cache_key: &str, credentials: &AwsCredentials) {
+    if tokio::fs::create_dir_all(cache_dir).await.is_err() {
+        return;
+    }
+    let cached = CachedCredentials {
+        access_key_id: credentials.aws_access_key_id().to_owned(),
+        secret_access_key: credentials.aws_secret_access_key().to_owned(),
+        session_token: credentials.token().clone(),
+        expiration: *credentials.expires_at(),
     };
-    let sts = StsClient::new_with(
-        HttpClient::new().unwrap(),
-        source_profile,
-        source_profile_region,
-    );
-    let provider = StsAssumeRoleSessionCredentialsProvider::new(
-        sts,
-        role_arn,
-        session_name,
-        None,
-        None,
-        None,
+    if let Ok(json) = serde_json::to_string(&cached) {
+        let _ = tokio::fs::write(credential_cache_file_path(cache_dir, cache_key), json).await;
+    }
+}
+
+// Wraps a resolved credentials provider with an on-disk cache keyed by the
+// session params that actually scope the AssumeRole call (see
+// `credential_cache_key`), so a new process can reuse a still-valid
+// assumed-role session instead of re-running AssumeRole (and, with MFA,
+// re-prompting).
+struct CachingCredentialsProvider {
+    inner: Box<dyn ProvideAwsCredentials + Send + Sync>,
+    cache_dir: PathBuf,
+    cache_key: String,
+}
+
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for CachingCredentialsProvider {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        if let Some(cached) = read_credentials_cache(&self.cache_dir, &self.cache_key).await {
+            return Ok(cached);
+        }
+        let credentials = self.inner.credentials().await?;
+        write_credentials_cache(&self.cache_dir, &self.cache_key, &credentials).await;
+        Ok(credentials)
+    }
+}
+
+// Merges `~/.aws/config` and `~/.aws/credentials` profile maps: credentials
+// file entries (access keys) take precedence over config file entries, while
+// config-only keys (`role_arn`, `source_profile`, `region`, ...) are kept.
+fn merge_config_maps(
+    config: HashMap<String, HashMap<String, String>>,
+    credentials: HashMap<String, HashMap<String, String>>,
+) -> HashMap<String, HashMap<String, String>> {
+    let mut merged = config;
+    for (profile, props) in credentials {
+        let entry = merged.entry(profile).or_insert_with(HashMap::new);
+        for (key, value) in props {
+            entry.insert(key, value);
+        }
+    }
+    merged
+}
+
+// Recursively resolves a profile into a credentials provider: profiles with a
+// `role_arn` are assumed via STS using the (recursively resolved) credentials
+// of their `source_profile`/`credential_source`, bottoming out at a static
+// provider built from the leaf profile's access keys. Also returns the
+// region the top-level call's own AssumeRole hit (`None` for a leaf profile,
+// which never assumes a role), so callers can talk to the same STS endpoint
+// that produced the returned credentials.
+//
+// The AssumeRole call itself is made against the `source_profile`'s own
+// declared region (falling back to `default_region`), not the region of the
+// profile doing the assuming: that matches the AWS CLI's behavior, and is
+// the only region a plain `source_profile` entry (which has no `role_arn` of
+// its own) can meaningfully declare. `credential_source` profiles have no
+// `source_profile` to read a region from, so they fall back to their own
+// `region` key instead.
+fn resolve_credentials_provider(
+    profile_name: &str,
+    config: &HashMap<String, HashMap<String, String>>,
+    default_region: &Region,
+    session_name: &str,
+    mfa_token_provider: Option<&MfaTokenProvider>,
+    visited: &mut HashSet<String>,
+    depth: usize,
+) -> Result<(Box<dyn ProvideAwsCredentials + Send + Sync>, Option<Region>), CredentialsError> {
+    if depth > MAX_SOURCE_PROFILE_DEPTH {
+        return Err(CredentialsError::new(
+            "source_profile chain exceeds the maximum allowed depth",
+        ));
+    }
+    if !visited.insert(profile_name.to_owned()) {
+        return Err(CredentialsError::new(format!(
+            "Cycle detected in source_profile chain at profile \"{}\"",
+            profile_name
+        )));
+    }
+    let profile = config.get(profile_name).ok_or_else(|| {
+        CredentialsError::new(format!(
+            "Failed to find profile \"{}\" in config file",
+            profile_name
+        ))
+    })?;
+
+    let role_arn = match profile.get(ROLE_ARN) {
+        Some(role_arn) => role_arn.to_owned(),
+        None => return static_provider_from_profile(profile_name, profile).map(|p| (p, None)),
+    };
+    let source_profile_name = profile.get(SOURCE_PROFILE);
+    let credential_source = profile.get(CREDENTIAL_SOURCE);
+    let source_provider: Box<dyn ProvideAwsCredentials + Send + Sync> =
+        match (source_profile_name, credential_source) {
+            (Some(_), Some(_)) => {
+                return Err(CredentialsError::new(
+                    "Profile must not specify both source_profile and credential_source",
+                ))
+            }
+            (Some(source_profile_name), None) => {
+                resolve_credentials_provider(
+                    source_profile_name,
+                    config,
+                    default_region,
+                    session_name,
+                    mfa_token_provider,
+                    visited,
+                    depth + 1,
+                )?
+                .0
+            }
+            (None, Some(credential_source)) => credential_source_provider(credential_source)?,
+            (None, None) => {
+                return Err(CredentialsError::new(
+                    "Profile with role_arn must specify either source_profile or credential_source",
+                ))
+            }
+        };
+
+    let mfa_serial = profile.get(MFA_SERIAL).map(std::borrow::ToOwned::to_owned);
+    let mfa_token_provider = match (&mfa_serial, mfa_token_provider) {
+        (Some(_), Some(provider)) => Some(provider.clone()),
+        (Some(_), None) => {
+            return Err(CredentialsError::new(
+                "Profile requires mfa_serial but no mfa_token_provider was configured",
+            ))
+        }
+        (None, _) => None,
+    };
+    let SessionParams {
+        role_session_name,
+        external_id,
+        policy,
+        duration_seconds,
+    } = parse_session_params(profile, session_name)?;
+    let session_duration = duration_seconds.map(Duration::seconds);
+
+    let region_profile_name = source_profile_name.map(String::as_str).unwrap_or(profile_name);
+    let region = config
+        .get(region_profile_name)
+        .and_then(|profile| profile.get("region"))
+        .and_then(|s| Region::from_str(s).ok())
+        .unwrap_or_else(|| default_region.clone());
+    let sts = StsClient::new_with(HttpClient::new().unwrap(), source_provider, region.clone());
+
+    // `StsAssumeRoleSessionCredentialsProvider` has no `mfa_serial`-only path
+    // for supplying the one-time token code, because it is meant to be
+    // re-assumed transparently on expiry and a TokenCode can't be reused for
+    // that: STS rejects it the second time. So for MFA-protected roles we
+    // drive AssumeRole ourselves, asking `mfa_token_provider` for a fresh
+    // code on every real assume (not just the first), and let
+    // `AutoRefreshingProvider` decide when that actually happens.
+    if let (Some(mfa_serial), Some(mfa_token_provider)) = (mfa_serial, mfa_token_provider) {
+        let provider = MfaAssumeRoleProvider {
+            sts,
+            role_arn,
+            role_session_name,
+            external_id,
+            duration_seconds,
+            policy,
+            mfa_serial,
+            mfa_token_provider,
+        };
+        let provider: Box<dyn ProvideAwsCredentials + Send + Sync> =
+            Box::new(rusoto_credential::AutoRefreshingProvider::new(provider)?);
+        Ok((provider, Some(region)))
+    } else {
+        let provider = StsAssumeRoleSessionCredentialsProvider::new(
+            sts,
+            role_arn,
+            role_session_name,
+            external_id,
+            session_duration,
+            policy,
+            None,
+        );
+        let provider: Box<dyn ProvideAwsCredentials + Send + Sync> =
+            Box::new(rusoto_credential::AutoRefreshingProvider::new(provider)?);
+        Ok((provider, Some(region)))
+    }
+}
+
+// Drives AssumeRole directly, rather than via
+// `StsAssumeRoleSessionCredentialsProvider`, so that `mfa_token_provider` is
+// invoked fresh for every real AssumeRole call (including ones triggered by
+// the wrapping `AutoRefreshingProvider` refreshing an expired session), not
+// just once when the provider is first constructed.
+struct MfaAssumeRoleProvider {
+    sts: StsClient,
+    role_arn: String,
+    role_session_name: String,
+    external_id: Option<String>,
+    duration_seconds: Option<i64>,
+    policy: Option<String>,
+    mfa_serial: String,
+    mfa_token_provider: MfaTokenProvider,
+}
+
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for MfaAssumeRoleProvider {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        let token_code = (self.mfa_token_provider)()?;
+        let response = self
+            .sts
+            .assume_role(AssumeRoleRequest {
+                role_arn: self.role_arn.clone(),
+                role_session_name: self.role_session_name.clone(),
+                external_id: self.external_id.clone(),
+                duration_seconds: self.duration_seconds,
+                policy: self.policy.clone(),
+                serial_number: Some(self.mfa_serial.clone()),
+                token_code: Some(token_code),
+                ..Default::default()
+            })
+            .await
+            .map_err(|err| CredentialsError::new(format!("Failed to call AssumeRole: {}", err)))?;
+        let credentials = response.credentials.ok_or_else(|| {
+            CredentialsError::new("AssumeRole response did not include credentials")
+        })?;
+        let expiration = credentials.expiration.parse::<DateTime<Utc>>().ok();
+        Ok(AwsCredentials::new(
+            credentials.access_key_id,
+            credentials.secret_access_key,
+            Some(credentials.session_token),
+            expiration,
+        ))
+    }
+}
+
+// Builds a static credentials provider from a leaf profile's access keys,
+// as merged from `~/.aws/credentials` and `~/.aws/config`.
+fn static_provider_from_profile(
+    profile_name: &str,
+    profile: &HashMap<String, String>,
+) -> Result<Box<dyn ProvideAwsCredentials + Send + Sync>, CredentialsError> {
+    let access_key_id = profile.get(AWS_ACCESS_KEY_ID).ok_or_else(|| {
+        CredentialsError::new(format!(
+            "Failed to find aws_access_key_id for profile \"{}\"",
+            profile_name
+        ))
+    })?;
+    let secret_access_key = profile.get(AWS_SECRET_ACCESS_KEY).ok_or_else(|| {
+        CredentialsError::new(format!(
+            "Failed to find aws_secret_access_key for profile \"{}\"",
+            profile_name
+        ))
+    })?;
+    let session_token = profile
+        .get(AWS_SESSION_TOKEN)
+        .map(std::borrow::ToOwned::to_owned);
+    Ok(Box::new(StaticProvider::new(
+        access_key_id.to_owned(),
+        secret_access_key.to_owned(),
+        session_token,
         None,
-    );
-    rusoto_credential::AutoRefreshingProvider::new(provider)
+    )))
+}
+
+// Builds the base credentials provider for a `credential_source` value, the
+// CLI-compatible alternative to `source_profile` for profiles whose base
+// credentials come from the instance/container role or the environment
+// rather than another profile.
+fn credential_source_provider(
+    credential_source: &str,
+) -> Result<Box<dyn ProvideAwsCredentials + Send + Sync>, CredentialsError> {
+    match credential_source {
+        CREDENTIAL_SOURCE_EC2_INSTANCE_METADATA => Ok(Box::new(InstanceMetadataProvider::new())),
+        CREDENTIAL_SOURCE_ECS_CONTAINER => Ok(Box::new(ContainerProvider::new())),
+        CREDENTIAL_SOURCE_ENVIRONMENT => Ok(Box::new(EnvironmentProvider::default())),
+        other => Err(CredentialsError::new(format!(
+            "Unsupported credential_source \"{}\"",
+            other
+        ))),
+    }
 }
 
 /////////////////////////////////////////
@@ -143,6 +674,7 @@ fn create_assume_role_profile(
 // Quoted from rusoto-credentials/profile.rs
 const AWS_PROFILE: &str = "AWS_PROFILE";
 const AWS_SHARED_CREDENTIALS_FILE: &str = "AWS_SHARED_CREDENTIALS_FILE";
+const AWS_CONFIG_FILE: &str = "AWS_CONFIG_FILE";
 const DEFAULT: &str = "default";
 
 // Quoted from rusoto-credentials/profile.rs
@@ -196,21 +728,31 @@ fn parse_config_file(file_path: &Path) -> Option<HashMap<String, HashMap<String,
     Some(result.0)
 }
 
-// Quoted from rusoto-credentials/profile.rs
-fn default_profile_location() -> Result<PathBuf, CredentialsError> {
+// Adapted from rusoto-credentials/profile.rs, but now also honors
+// AWS_CONFIG_FILE and reads `~/.aws/credentials` in addition to
+// `~/.aws/config` (see `merge_config_maps`) instead of substituting one for
+// the other.
+fn credentials_file_location() -> Result<PathBuf, CredentialsError> {
     let env = non_empty_env_var(AWS_SHARED_CREDENTIALS_FILE);
     match env {
         Some(path) => Ok(PathBuf::from(path)),
-        None => hardcoded_profile_location(),
+        None => hardcoded_aws_file_location("credentials"),
     }
 }
 
-// Quoted from rusoto-credentials/profile.rs
-fn hardcoded_profile_location() -> Result<PathBuf, CredentialsError> {
+fn config_file_location() -> Result<PathBuf, CredentialsError> {
+    let env = non_empty_env_var(AWS_CONFIG_FILE);
+    match env {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => hardcoded_aws_file_location("config"),
+    }
+}
+
+fn hardcoded_aws_file_location(file_name: &str) -> Result<PathBuf, CredentialsError> {
     match home_dir() {
         Some(mut home_path) => {
             home_path.push(".aws");
-            home_path.push("config"); // <<<<<<<<<< NOTE: original value is "credentials", but now "config".
+            home_path.push(file_name);
             Ok(home_path)
         }
         None => Err(CredentialsError::new("Failed to determine home directory.")),
@@ -235,3 +777,346 @@ fn non_empty_env_var(name: &str) -> Option<String> {
         Err(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_credentials_provider_detects_source_profile_cycle() {
+        let mut config = HashMap::new();
+        config.insert(
+            "a".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/a"),
+                (SOURCE_PROFILE, "b"),
+            ]),
+        );
+        config.insert(
+            "b".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/b"),
+                (SOURCE_PROFILE, "a"),
+            ]),
+        );
+
+        let err = resolve_credentials_provider(
+            "a",
+            &config,
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            0,
+        )
+        .err()
+        .expect("expected a cycle error");
+        assert!(err.to_string().contains("Cycle detected"));
+    }
+
+    #[test]
+    fn merge_config_maps_prefers_credentials_file_on_overlapping_keys() {
+        let mut config = HashMap::new();
+        config.insert(
+            "default".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/x"),
+                (AWS_ACCESS_KEY_ID, "CONFIG_KEY"),
+            ]),
+        );
+        let mut credentials = HashMap::new();
+        credentials.insert(
+            "default".to_string(),
+            profile(&[(AWS_ACCESS_KEY_ID, "CREDS_KEY")]),
+        );
+
+        let merged = merge_config_maps(config, credentials);
+        let profile = merged.get("default").expect("profile should be present");
+        assert_eq!(profile.get(AWS_ACCESS_KEY_ID).unwrap(), "CREDS_KEY");
+        assert_eq!(
+            profile.get(ROLE_ARN).unwrap(),
+            "arn:aws:iam::111111111111:role/x"
+        );
+    }
+
+    #[test]
+    fn merge_config_maps_keeps_profiles_only_present_in_one_file() {
+        let mut config = HashMap::new();
+        config.insert(
+            "config-only".to_string(),
+            profile(&[(ROLE_ARN, "arn:aws:iam::111111111111:role/x")]),
+        );
+        let mut credentials = HashMap::new();
+        credentials.insert(
+            "credentials-only".to_string(),
+            profile(&[(AWS_ACCESS_KEY_ID, "CREDS_KEY")]),
+        );
+
+        let merged = merge_config_maps(config, credentials);
+        assert!(merged.contains_key("config-only"));
+        assert!(merged.contains_key("credentials-only"));
+    }
+
+    #[test]
+    fn parse_session_params_reads_all_session_tuning_keys() {
+        let profile = profile(&[
+            (ROLE_SESSION_NAME, "custom-session"),
+            (EXTERNAL_ID, "ext-id"),
+            (POLICY, "{\"Version\":\"2012-10-17\"}"),
+            (DURATION_SECONDS, "900"),
+        ]);
+
+        let params = parse_session_params(&profile, "default-session").expect("should parse");
+        assert_eq!(params.role_session_name, "custom-session");
+        assert_eq!(params.external_id.as_deref(), Some("ext-id"));
+        assert_eq!(
+            params.policy.as_deref(),
+            Some("{\"Version\":\"2012-10-17\"}")
+        );
+        assert_eq!(params.duration_seconds, Some(900));
+    }
+
+    #[test]
+    fn parse_session_params_falls_back_to_default_session_name() {
+        let params = parse_session_params(&profile(&[]), "default-session").expect("should parse");
+        assert_eq!(params.role_session_name, "default-session");
+        assert_eq!(params.external_id, None);
+        assert_eq!(params.policy, None);
+        assert_eq!(params.duration_seconds, None);
+    }
+
+    #[test]
+    fn parse_session_params_rejects_non_integer_duration_seconds() {
+        let profile = profile(&[(DURATION_SECONDS, "not-a-number")]);
+        let err = parse_session_params(&profile, "session")
+            .err()
+            .expect("expected an error");
+        assert!(err.to_string().contains("as an integer"));
+    }
+
+    #[test]
+    fn credential_cache_key_is_deterministic() {
+        let params = SessionParams {
+            role_session_name: "session".to_string(),
+            external_id: Some("ext".to_string()),
+            policy: Some("{}".to_string()),
+            duration_seconds: Some(900),
+        };
+        let a = credential_cache_key("arn:aws:iam::111111111111:role/x", &params);
+        let b = credential_cache_key("arn:aws:iam::111111111111:role/x", &params);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn credential_cache_key_differs_by_session_params() {
+        let base = SessionParams {
+            role_session_name: "session".to_string(),
+            external_id: None,
+            policy: None,
+            duration_seconds: None,
+        };
+        let with_policy = SessionParams {
+            policy: Some("{}".to_string()),
+            ..base.clone()
+        };
+        let with_external_id = SessionParams {
+            external_id: Some("ext".to_string()),
+            ..base.clone()
+        };
+
+        let role_arn = "arn:aws:iam::111111111111:role/x";
+        let base_key = credential_cache_key(role_arn, &base);
+        let policy_key = credential_cache_key(role_arn, &with_policy);
+        let external_id_key = credential_cache_key(role_arn, &with_external_id);
+
+        assert_ne!(base_key, policy_key);
+        assert_ne!(base_key, external_id_key);
+        assert_ne!(policy_key, external_id_key);
+    }
+
+    #[tokio::test]
+    async fn credentials_cache_round_trips_and_respects_expiry_skew() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "rusoto-config-assume-role-credential-test-{}-{}",
+            std::process::id(),
+            "round-trip"
+        ));
+        let cache_key = "test-key";
+
+        let credentials = AwsCredentials::new(
+            "AKIDEXAMPLE".to_string(),
+            "secret".to_string(),
+            Some("token".to_string()),
+            Some(Utc::now() + Duration::minutes(10)),
+        );
+        write_credentials_cache(&cache_dir, cache_key, &credentials).await;
+        let cached = read_credentials_cache(&cache_dir, cache_key).await;
+        assert_eq!(
+            cached.map(|c| c.aws_access_key_id().to_owned()),
+            Some("AKIDEXAMPLE".to_string())
+        );
+
+        // An entry expiring within the skew margin is treated as absent.
+        let soon_expiring = AwsCredentials::new(
+            "AKIDEXAMPLE".to_string(),
+            "secret".to_string(),
+            None,
+            Some(Utc::now() + Duration::seconds(1)),
+        );
+        write_credentials_cache(&cache_dir, cache_key, &soon_expiring).await;
+        assert!(read_credentials_cache(&cache_dir, cache_key).await.is_none());
+
+        let _ = tokio::fs::remove_dir_all(&cache_dir).await;
+    }
+
+    #[test]
+    fn resolve_credentials_provider_requires_mfa_token_provider_when_mfa_serial_set() {
+        let mut config = HashMap::new();
+        config.insert(
+            "default".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/x"),
+                (SOURCE_PROFILE, "prod"),
+                (MFA_SERIAL, "arn:aws:iam::111111111111:mfa/user"),
+            ]),
+        );
+        config.insert(
+            "prod".to_string(),
+            profile(&[
+                (AWS_ACCESS_KEY_ID, "AKIDEXAMPLE"),
+                (AWS_SECRET_ACCESS_KEY, "secret"),
+            ]),
+        );
+
+        let err = resolve_credentials_provider(
+            "default",
+            &config,
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            0,
+        )
+        .err()
+        .expect("expected an error");
+        assert!(err
+            .to_string()
+            .contains("no mfa_token_provider was configured"));
+    }
+
+    #[test]
+    fn resolve_credentials_provider_assumes_role_in_source_profiles_region() {
+        let mut config = HashMap::new();
+        config.insert(
+            "default".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/x"),
+                (SOURCE_PROFILE, "prod"),
+                ("region", "us-east-1"),
+            ]),
+        );
+        config.insert(
+            "prod".to_string(),
+            profile(&[
+                (AWS_ACCESS_KEY_ID, "AKIDEXAMPLE"),
+                (AWS_SECRET_ACCESS_KEY, "secret"),
+                ("region", "us-west-2"),
+            ]),
+        );
+
+        let (_, region) = resolve_credentials_provider(
+            "default",
+            &config,
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            0,
+        )
+        .expect("should resolve");
+        // The AssumeRole call hits `prod`'s declared region, not `default`'s
+        // own `region` key or the caller-supplied `default_region`.
+        assert_eq!(region, Some(Region::UsWest2));
+    }
+
+    #[test]
+    fn credential_source_provider_rejects_unsupported_value() {
+        let err = credential_source_provider("SomeOtherSource")
+            .err()
+            .expect("expected an error");
+        assert!(err.to_string().contains("Unsupported credential_source"));
+    }
+
+    #[test]
+    fn resolve_credentials_provider_rejects_both_source_profile_and_credential_source() {
+        let mut config = HashMap::new();
+        config.insert(
+            "default".to_string(),
+            profile(&[
+                (ROLE_ARN, "arn:aws:iam::111111111111:role/x"),
+                (SOURCE_PROFILE, "prod"),
+                (CREDENTIAL_SOURCE, CREDENTIAL_SOURCE_ENVIRONMENT),
+            ]),
+        );
+
+        let err = resolve_credentials_provider(
+            "default",
+            &config,
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            0,
+        )
+        .err()
+        .expect("expected an error");
+        assert!(err.to_string().contains("must not specify both"));
+    }
+
+    #[test]
+    fn resolve_credentials_provider_requires_source_profile_or_credential_source() {
+        let mut config = HashMap::new();
+        config.insert(
+            "default".to_string(),
+            profile(&[(ROLE_ARN, "arn:aws:iam::111111111111:role/x")]),
+        );
+
+        let err = resolve_credentials_provider(
+            "default",
+            &config,
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            0,
+        )
+        .err()
+        .expect("expected an error");
+        assert!(err
+            .to_string()
+            .contains("must specify either source_profile or credential_source"));
+    }
+
+    #[test]
+    fn resolve_credentials_provider_enforces_max_source_profile_depth() {
+        let err = resolve_credentials_provider(
+            "whatever",
+            &HashMap::new(),
+            &Region::UsEast1,
+            "session",
+            None,
+            &mut HashSet::new(),
+            MAX_SOURCE_PROFILE_DEPTH + 1,
+        )
+        .err()
+        .expect("expected a max-depth error");
+        assert!(err.to_string().contains("maximum allowed depth"));
+    }
+}